@@ -1,11 +1,9 @@
 extern crate reed_solomon_erasure;
 
-use reed_solomon_erasure::galois_8::ReedSolomon;
-// or use the following for Galois 2^16 backend
-// use reed_solomon_erasure::galois_16::ReedSolomon;
 use criterion::BenchmarkId;
 use criterion::Criterion;
 use criterion::{criterion_group, criterion_main, BatchSize, Throughput};
+use ecrt::ec::ErasureCoder;
 use rand::prelude::*;
 use rand::Rng;
 
@@ -13,10 +11,12 @@ fn create_shards(
     shard_size: usize,
     num_data: usize,
     num_parity: usize,
-) -> (ReedSolomon, Vec<Vec<u8>>) {
+) -> (ErasureCoder, Vec<Vec<u8>>) {
     assert!(shard_size > 0 && num_data > 0);
 
-    let r = ReedSolomon::new(num_data, num_parity).unwrap();
+    // Picks galois_8 or galois_16 depending on num_data + num_parity, so
+    // benchmarks can exercise shard counts above the galois_8 256 cap.
+    let r = ErasureCoder::new(num_data, num_parity).unwrap();
 
     let mut shards = vec![vec![0u8; shard_size]; num_data + num_parity];
     // leave parity shards as 0 data
@@ -32,14 +32,14 @@ fn create_and_encode_shards(
     shard_size: usize,
     num_data: usize,
     num_parity: usize,
-) -> (ReedSolomon, Vec<Vec<u8>>) {
+) -> (ErasureCoder, Vec<Vec<u8>>) {
     let (r, mut shards) = create_shards(shard_size, num_data, num_parity);
     r.encode(&mut shards).unwrap();
     // Construct the parity shards
     (r, shards)
 }
 
-fn decode_shards(r: ReedSolomon, shards: Vec<Vec<u8>>, num_lost: usize) {
+fn decode_shards(r: ErasureCoder, shards: Vec<Vec<u8>>, num_lost: usize) {
     // Make a copy and transform it into option shards arrangement
     // for feeding into reconstruct_shards
     let mut shards: Vec<_> = shards.iter().cloned().map(Some).collect();
@@ -2,12 +2,13 @@ extern crate reed_solomon_erasure;
 
 use criterion::{criterion_group, criterion_main, BatchSize, Throughput};
 
-use reed_solomon_erasure::galois_8::ReedSolomon;
-// or use the following for Galois 2^16 backend
-// use reed_solomon_erasure::galois_16::ReedSolomon;
 use criterion::async_executor::AsyncStdExecutor;
 use criterion::BenchmarkId;
 use criterion::Criterion;
+use ecrt::ec::ErasureCoder;
+use ecrt::manifest::{build_root_node, decode_from_root, EcManifest};
+use ecrt::merkle::{Hash, MerkleTree};
+use ecrt::retrieval::{retrieve_min_k, ShardLink};
 use futures::{pin_mut, prelude::*};
 use graphsync::{GraphSync, GraphSyncEvent, Request};
 use ipld_traversal::{
@@ -47,13 +48,14 @@ fn create_shards(
     shard_size: usize,
     num_data: usize,
     num_parity: usize,
-) -> (MemoryBlockstore, Cid, ReedSolomon) {
+) -> (MemoryBlockstore, Cid, ErasureCoder, Hash, Vec<ShardLink>) {
     assert!(shard_size > 0);
     let store = MemoryBlockstore::new();
     let lsys = LinkSystem::new(store.clone());
     let mut links = Vec::new();
+    let mut shard_links = Vec::new();
 
-    let r = ReedSolomon::new(num_data, num_parity).unwrap(); // 3 data shards, 2 parity shards
+    let r = ErasureCoder::new(num_data, num_parity).unwrap(); // num_data data shards, num_parity parity shards
 
     let mut shards = vec![vec![0u8; shard_size]; num_data + num_parity];
     // leave parity shards as 0 data
@@ -65,7 +67,13 @@ fn create_shards(
     // Construct the parity shards
     r.encode(&mut shards).unwrap();
 
-    for s in shards.into_iter() {
+    // Commit to every shard in the erasure set so a receiver can verify
+    // one as it arrives, instead of only detecting corruption once the
+    // whole DAG fails to reconstruct.
+    let tree = MerkleTree::from_shards(&shards);
+    let root_hash = tree.root();
+
+    for (i, s) in shards.into_iter().enumerate() {
         // each entry is 8-bit so the len is the number of bytes
         let size = s.len();
         if size == 0 {
@@ -75,18 +83,32 @@ fn create_shards(
         let cid = lsys
             .store(Prefix::new(0x55, 0x12), &Ipld::Bytes(s.clone()))
             .expect("link system should store shard");
+        let proof = tree.proof(i);
         links.push(ipld!({
             "Hash": cid,
             "Tsize": size,
+            "MerkleIndex": proof.index as u64,
+            "MerkleSiblings": proof.siblings.iter().map(|h| Ipld::Bytes(h.to_vec())).collect::<Vec<_>>(),
         }));
+        shard_links.push(ShardLink {
+            index: i,
+            cid,
+            proof,
+        });
     }
-    let root_node = ipld!({
-        "Links": links,
-    });
+    let manifest = EcManifest {
+        num_data,
+        num_parity,
+        shard_size,
+        payload_len: shard_size * num_data,
+        field: r.field(),
+        merkle_root: root_hash,
+    };
+    let root_node = build_root_node(links, manifest);
     let root = lsys
         .store(Prefix::new(0x71, 0x12), &root_node)
         .expect("link system to store root node");
-    (store, root, r)
+    (store, root, r, root_hash, shard_links)
 }
 
 async fn run_local_transfer(store: MemoryBlockstore, root: Cid) {
@@ -175,7 +197,105 @@ fn bench_ec_graphsync(c: &mut Criterion) {
             move |b, &num_data| {
                 b.to_async(AsyncStdExecutor).iter_batched(
                     || create_shards(KB, num_data, num_data),
-                    |(store, root, _)| async move { run_local_transfer(store, root).await },
+                    |(store, root, _, _, _)| async move { run_local_transfer(store, root).await },
+                    BatchSize::SmallInput,
+                );
+            },
+        );
+    }
+}
+
+/// Drives the same transfer as `run_local_transfer`, but through
+/// `retrieve_min_k`: requests are issued per-shard and the transfer stops
+/// as soon as `num_data` verified shards are in hand, instead of walking
+/// the full `num_data + num_parity` DAG.
+async fn run_min_k_transfer(
+    store: MemoryBlockstore,
+    coder: ErasureCoder,
+    merkle_root: Hash,
+    shard_links: Vec<ShardLink>,
+) {
+    let num_data = coder.data_shard_count();
+
+    let (peer1, trans) = mk_transport();
+    let mut swarm1 = Swarm::new(trans, GraphSync::new(store), peer1);
+
+    Swarm::listen_on(&mut swarm1, "/ip4/127.0.0.1/tcp/0".parse().unwrap()).unwrap();
+
+    let listener_addr = async {
+        loop {
+            let swarm1_fut = swarm1.select_next_some();
+            pin_mut!(swarm1_fut);
+            match swarm1_fut.await {
+                SwarmEvent::NewListenAddr { address, .. } => return address,
+                _ => {}
+            }
+        }
+    }
+    .await;
+
+    let (peer2, trans) = mk_transport();
+    let mut swarm2 = Swarm::new(trans, GraphSync::new(MemoryBlockstore::new()), peer2);
+    swarm2.behaviour_mut().add_address(&peer1, listener_addr);
+
+    let driver = retrieve_min_k(&mut swarm2, peer1, &shard_links, merkle_root, num_data, &coder);
+    pin_mut!(driver);
+
+    loop {
+        let swarm1_fut = swarm1.select_next_some();
+        pin_mut!(swarm1_fut);
+
+        match future::select(swarm1_fut, &mut driver).await {
+            future::Either::Right((result, _)) => {
+                result.expect("min-k retrieval should recover num_data shards");
+                return;
+            }
+            future::Either::Left(_) => continue,
+        }
+    }
+}
+
+fn bench_min_k_graphsync(c: &mut Criterion) {
+    static KB: usize = 1024;
+
+    let mut group = c.benchmark_group("ec-graphsync-min-k");
+
+    for num_data in [10, 100, 250].iter() {
+        group.throughput(Throughput::Bytes(*num_data as u64));
+        group.bench_with_input(
+            BenchmarkId::new("varying number of data size", num_data),
+            num_data,
+            move |b, &num_data| {
+                b.to_async(AsyncStdExecutor).iter_batched(
+                    || create_shards(KB, num_data, num_data),
+                    |(store, _root, coder, merkle_root, shard_links)| async move {
+                        run_min_k_transfer(store, coder, merkle_root, shard_links).await
+                    },
+                    BatchSize::SmallInput,
+                );
+            },
+        );
+    }
+}
+
+/// Decodes straight from the local store (no GraphSync involved), the
+/// same path a peer follows once it already has every shard block.
+fn bench_decode_from_root(c: &mut Criterion) {
+    static KB: usize = 1024;
+
+    let mut group = c.benchmark_group("decode-from-root");
+
+    for num_data in [10, 100, 250].iter() {
+        group.throughput(Throughput::Bytes(*num_data as u64));
+        group.bench_with_input(
+            BenchmarkId::new("varying number of data size", num_data),
+            num_data,
+            |b, &num_data| {
+                b.iter_batched(
+                    || create_shards(KB, num_data, num_data),
+                    |(store, root, _, _, _)| {
+                        decode_from_root(store, &root).expect("decode should succeed");
+                    },
                     BatchSize::SmallInput,
                 );
             },
@@ -183,5 +303,10 @@ fn bench_ec_graphsync(c: &mut Criterion) {
     }
 }
 
-criterion_group!(benches, bench_ec_graphsync);
+criterion_group!(
+    benches,
+    bench_ec_graphsync,
+    bench_min_k_graphsync,
+    bench_decode_from_root
+);
 criterion_main!(benches);
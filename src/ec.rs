@@ -0,0 +1,96 @@
+//! Picks `galois_8` or `galois_16` based on total shard count, behind a
+//! single `encode`/`reconstruct`/`verify` API.
+
+use reed_solomon_erasure::galois_16::ReedSolomon as ReedSolomon16;
+use reed_solomon_erasure::galois_8::ReedSolomon as ReedSolomon8;
+use reed_solomon_erasure::Error;
+
+/// Total shard count above which `galois_8` can no longer represent every
+/// shard index and we must switch to `galois_16`.
+const GALOIS_8_MAX_SHARDS: usize = 256;
+
+/// Which Galois field backend an [`ErasureCoder`] ended up using. Stored
+/// alongside the coder so it can be persisted (e.g. in an EC manifest)
+/// and the same field can be reselected on the decode side.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GaloisField {
+    Field8,
+    Field16,
+}
+
+impl GaloisField {
+    /// The field `ErasureCoder::new` would select for this many total shards.
+    pub fn for_shard_count(total_shards: usize) -> Self {
+        if total_shards <= GALOIS_8_MAX_SHARDS {
+            GaloisField::Field8
+        } else {
+            GaloisField::Field16
+        }
+    }
+}
+
+/// Reed-Solomon coder that transparently selects `galois_8` or
+/// `galois_16` based on `num_data + num_parity`, exposing a single API
+/// regardless of which backend was chosen.
+pub enum ErasureCoder {
+    Field8(ReedSolomon8),
+    Field16(ReedSolomon16),
+}
+
+impl ErasureCoder {
+    /// Build a coder for `num_data` data shards and `num_parity` parity
+    /// shards, picking `galois_16` once the total exceeds what `galois_8`
+    /// can address.
+    pub fn new(num_data: usize, num_parity: usize) -> Result<Self, Error> {
+        match GaloisField::for_shard_count(num_data + num_parity) {
+            GaloisField::Field8 => Ok(Self::Field8(ReedSolomon8::new(num_data, num_parity)?)),
+            GaloisField::Field16 => Ok(Self::Field16(ReedSolomon16::new(num_data, num_parity)?)),
+        }
+    }
+
+    /// Which field this coder ended up using.
+    pub fn field(&self) -> GaloisField {
+        match self {
+            Self::Field8(_) => GaloisField::Field8,
+            Self::Field16(_) => GaloisField::Field16,
+        }
+    }
+
+    pub fn data_shard_count(&self) -> usize {
+        match self {
+            Self::Field8(r) => r.data_shard_count(),
+            Self::Field16(r) => r.data_shard_count(),
+        }
+    }
+
+    pub fn parity_shard_count(&self) -> usize {
+        match self {
+            Self::Field8(r) => r.parity_shard_count(),
+            Self::Field16(r) => r.parity_shard_count(),
+        }
+    }
+
+    /// Fill the parity shards in place from the already-populated data shards.
+    pub fn encode(&self, shards: &mut [Vec<u8>]) -> Result<(), Error> {
+        match self {
+            Self::Field8(r) => r.encode(shards),
+            Self::Field16(r) => r.encode(shards),
+        }
+    }
+
+    /// Recover any `None` shards from the shards that are present.
+    pub fn reconstruct(&self, shards: &mut [Option<Vec<u8>>]) -> Result<(), Error> {
+        match self {
+            Self::Field8(r) => r.reconstruct(shards),
+            Self::Field16(r) => r.reconstruct(shards),
+        }
+    }
+
+    /// Check that the parity shards are consistent with the data shards.
+    pub fn verify(&self, shards: &[Vec<u8>]) -> Result<bool, Error> {
+        match self {
+            Self::Field8(r) => r.verify(shards),
+            Self::Field16(r) => r.verify(shards),
+        }
+    }
+}
@@ -0,0 +1,6 @@
+pub mod ec;
+pub mod manifest;
+pub mod merkle;
+pub mod multi_peer;
+pub mod retrieval;
+pub mod streaming;
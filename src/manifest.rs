@@ -0,0 +1,270 @@
+//! Self-describing EC manifest embedded in the root node, so any peer
+//! with just the root CID can rebuild the coder and decode.
+
+use crate::ec::{ErasureCoder, GaloisField};
+use crate::merkle::{verify_shard, Hash, MerkleProof};
+use ipld_traversal::{blockstore::Blockstore, LinkSystem};
+use libipld::{ipld, Cid, Ipld};
+
+/// Upper bound on `num_data + num_parity` a manifest may claim: the
+/// largest total shard count `galois_16` can index.
+const MAX_TOTAL_SHARDS: usize = 65_536;
+/// Upper bound on `shard_size`, well past anything this crate actually
+/// produces, so a manifest from an untrusted peer can't force an
+/// oversized allocation before we've verified a single shard.
+const MAX_SHARD_SIZE: usize = 1 << 26;
+
+/// Everything a peer needs to rebuild the `ErasureCoder` used to encode
+/// a payload, and to recover the original bytes once enough shards are
+/// reconstructed.
+#[derive(Debug, Clone, Copy)]
+pub struct EcManifest {
+    pub num_data: usize,
+    pub num_parity: usize,
+    pub shard_size: usize,
+    pub payload_len: usize,
+    pub field: GaloisField,
+    pub merkle_root: Hash,
+}
+
+#[derive(Debug)]
+pub enum DecodeError {
+    MissingBlock(Cid),
+    MalformedRootNode,
+    MissingManifest,
+    TooFewShards { have: usize, need: usize },
+    Reconstruct(reed_solomon_erasure::Error),
+}
+
+impl EcManifest {
+    /// Rebuild the coder this manifest describes.
+    pub fn coder(&self) -> Result<ErasureCoder, reed_solomon_erasure::Error> {
+        ErasureCoder::new(self.num_data, self.num_parity)
+    }
+
+    fn to_ipld(self) -> Ipld {
+        let field = match self.field {
+            GaloisField::Field8 => "galois_8",
+            GaloisField::Field16 => "galois_16",
+        };
+        ipld!({
+            "Coding": "reed-solomon",
+            "Field": field,
+            "NumData": self.num_data as u64,
+            "NumParity": self.num_parity as u64,
+            "ShardSize": self.shard_size as u64,
+            "PayloadLen": self.payload_len as u64,
+            "MerkleRoot": Ipld::Bytes(self.merkle_root.to_vec()),
+        })
+    }
+
+    fn from_ipld(ipld: &Ipld) -> Option<Self> {
+        let map = match ipld {
+            Ipld::Map(m) => m,
+            _ => return None,
+        };
+        let as_usize = |key: &str| match map.get(key) {
+            Some(Ipld::Integer(i)) => Some(*i as usize),
+            _ => None,
+        };
+        let field = match map.get("Field") {
+            Some(Ipld::String(s)) if s == "galois_8" => GaloisField::Field8,
+            Some(Ipld::String(s)) if s == "galois_16" => GaloisField::Field16,
+            _ => return None,
+        };
+        let merkle_root = match map.get("MerkleRoot") {
+            Some(Ipld::Bytes(b)) if b.len() == 32 => {
+                let mut root = [0u8; 32];
+                root.copy_from_slice(b);
+                root
+            }
+            _ => return None,
+        };
+        let num_data = as_usize("NumData")?;
+        let num_parity = as_usize("NumParity")?;
+        let shard_size = as_usize("ShardSize")?;
+        let payload_len = as_usize("PayloadLen")?;
+
+        // The manifest comes from a root node we haven't verified a single
+        // shard of yet, so an attacker-inflated value here must not reach
+        // `Vec::with_capacity` or `ErasureCoder::new` unchecked.
+        if num_data == 0 || num_parity == 0 {
+            return None;
+        }
+        let total_shards = num_data.checked_add(num_parity)?;
+        if total_shards > MAX_TOTAL_SHARDS {
+            return None;
+        }
+        if shard_size == 0 || shard_size > MAX_SHARD_SIZE {
+            return None;
+        }
+        let capacity = num_data.checked_mul(shard_size)?;
+        if payload_len > capacity {
+            return None;
+        }
+
+        Some(Self {
+            num_data,
+            num_parity,
+            shard_size,
+            payload_len,
+            field,
+            merkle_root,
+        })
+    }
+}
+
+/// Build the root node for an erasure set: the shard links (as produced
+/// by `create_shards`) plus a manifest describing how to decode them.
+pub fn build_root_node(links: Vec<Ipld>, manifest: EcManifest) -> Ipld {
+    ipld!({
+        "Links": links,
+        "Manifest": manifest.to_ipld(),
+    })
+}
+
+/// Pull the `MerkleIndex`/`MerkleSiblings` a shard link was stored with
+/// back out into a `MerkleProof`.
+fn link_proof(link: &Ipld) -> Option<MerkleProof> {
+    let map = match link {
+        Ipld::Map(m) => m,
+        _ => return None,
+    };
+    let index = match map.get("MerkleIndex") {
+        Some(Ipld::Integer(i)) => *i as usize,
+        _ => return None,
+    };
+    let siblings = match map.get("MerkleSiblings") {
+        Some(Ipld::List(l)) => l
+            .iter()
+            .map(|h| match h {
+                Ipld::Bytes(b) if b.len() == 32 => {
+                    let mut sibling = [0u8; 32];
+                    sibling.copy_from_slice(b);
+                    Some(sibling)
+                }
+                _ => None,
+            })
+            .collect::<Option<Vec<Hash>>>()?,
+        _ => return None,
+    };
+    Some(MerkleProof { index, siblings })
+}
+
+/// Read the manifest and shard links from `root`, gather whichever
+/// shards are present in `store` and verify against the manifest's
+/// Merkle root, reconstruct, and return the original unpadded payload.
+/// A shard that fails `verify_shard` is treated the same as a missing
+/// one rather than fed to `reconstruct`.
+pub fn decode_from_root<B: Blockstore>(store: B, root: &Cid) -> Result<Vec<u8>, DecodeError> {
+    let lsys = LinkSystem::new(store);
+    let root_node: Ipld = lsys
+        .load(root)
+        .map_err(|_| DecodeError::MissingBlock(*root))?;
+
+    let map = match &root_node {
+        Ipld::Map(m) => m,
+        _ => return Err(DecodeError::MalformedRootNode),
+    };
+    let manifest =
+        EcManifest::from_ipld(map.get("Manifest").ok_or(DecodeError::MissingManifest)?)
+            .ok_or(DecodeError::MissingManifest)?;
+    let links = match map.get("Links") {
+        Some(Ipld::List(l)) => l,
+        _ => return Err(DecodeError::MalformedRootNode),
+    };
+
+    let mut shards: Vec<Option<Vec<u8>>> = Vec::with_capacity(links.len());
+    let mut available = 0;
+    for link in links {
+        let cid = match link {
+            Ipld::Map(m) => match m.get("Hash") {
+                Some(Ipld::Link(cid)) => *cid,
+                _ => return Err(DecodeError::MalformedRootNode),
+            },
+            _ => return Err(DecodeError::MalformedRootNode),
+        };
+        let proof = link_proof(link).ok_or(DecodeError::MalformedRootNode)?;
+
+        match lsys.load::<Ipld>(&cid) {
+            Ok(Ipld::Bytes(bytes)) if verify_shard(&manifest.merkle_root, &bytes, &proof) => {
+                available += 1;
+                shards.push(Some(bytes));
+            }
+            // Unverifiable or unreadable: treat exactly like a missing shard.
+            _ => shards.push(None),
+        }
+    }
+
+    if available < manifest.num_data {
+        return Err(DecodeError::TooFewShards {
+            have: available,
+            need: manifest.num_data,
+        });
+    }
+
+    let coder = manifest.coder().map_err(DecodeError::Reconstruct)?;
+    coder
+        .reconstruct(&mut shards)
+        .map_err(DecodeError::Reconstruct)?;
+
+    let mut payload = Vec::with_capacity(manifest.payload_len);
+    for shard in shards.into_iter().take(manifest.num_data) {
+        payload.extend_from_slice(&shard.expect("reconstruct fills every shard"));
+    }
+    payload.truncate(manifest.payload_len);
+
+    Ok(payload)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn manifest() -> EcManifest {
+        EcManifest {
+            num_data: 3,
+            num_parity: 2,
+            shard_size: 16,
+            payload_len: 40,
+            field: GaloisField::Field8,
+            merkle_root: [7u8; 32],
+        }
+    }
+
+    #[test]
+    fn a_manifest_round_trips_through_ipld() {
+        let original = manifest();
+        let round_tripped = EcManifest::from_ipld(&original.to_ipld()).unwrap();
+
+        assert_eq!(round_tripped.num_data, original.num_data);
+        assert_eq!(round_tripped.num_parity, original.num_parity);
+        assert_eq!(round_tripped.shard_size, original.shard_size);
+        assert_eq!(round_tripped.payload_len, original.payload_len);
+        assert_eq!(round_tripped.field, original.field);
+        assert_eq!(round_tripped.merkle_root, original.merkle_root);
+    }
+
+    #[test]
+    fn a_payload_len_beyond_data_capacity_is_rejected() {
+        let mut bloated = manifest();
+        // More than `num_data * shard_size` can actually hold.
+        bloated.payload_len = bloated.num_data * bloated.shard_size + 1;
+        assert!(EcManifest::from_ipld(&bloated.to_ipld()).is_none());
+    }
+
+    #[test]
+    fn an_implausible_shard_count_is_rejected() {
+        let mut bloated = manifest();
+        bloated.num_data = MAX_TOTAL_SHARDS;
+        bloated.num_parity = MAX_TOTAL_SHARDS;
+        assert!(EcManifest::from_ipld(&bloated.to_ipld()).is_none());
+    }
+
+    #[test]
+    fn a_zero_shard_size_is_rejected() {
+        let mut bloated = manifest();
+        bloated.shard_size = 0;
+        assert!(EcManifest::from_ipld(&bloated.to_ipld()).is_none());
+    }
+}
@@ -0,0 +1,139 @@
+//! Binary Merkle commitment over an erasure set's shards, so a receiver
+//! can verify one shard as it arrives instead of the whole DAG.
+
+use sha2::{Digest, Sha256};
+
+pub type Hash = [u8; 32];
+
+pub(crate) fn hash_leaf(shard: &[u8]) -> Hash {
+    let mut hasher = Sha256::new();
+    hasher.update(shard);
+    hasher.finalize().into()
+}
+
+pub(crate) fn hash_node(left: &Hash, right: &Hash) -> Hash {
+    let mut hasher = Sha256::new();
+    hasher.update(left);
+    hasher.update(right);
+    hasher.finalize().into()
+}
+
+/// Sibling hashes from a leaf up to the root, plus the leaf's index
+/// (which also encodes left/right at each level).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MerkleProof {
+    pub index: usize,
+    pub siblings: Vec<Hash>,
+}
+
+/// A Merkle tree over the hashes of an erasure set's `n = num_data +
+/// num_parity` shards. A level with an odd number of nodes duplicates
+/// its last node before pairing, same as the construction it mirrors.
+pub struct MerkleTree {
+    levels: Vec<Vec<Hash>>,
+}
+
+impl MerkleTree {
+    /// Build a tree whose leaves are the hashes of `shards`, in shard order.
+    pub fn from_shards(shards: &[Vec<u8>]) -> Self {
+        assert!(!shards.is_empty());
+
+        let mut level: Vec<Hash> = shards.iter().map(|s| hash_leaf(s)).collect();
+        let mut levels = vec![level.clone()];
+        while level.len() > 1 {
+            if level.len() % 2 == 1 {
+                level.push(*level.last().unwrap());
+            }
+            level = level
+                .chunks(2)
+                .map(|pair| hash_node(&pair[0], &pair[1]))
+                .collect();
+            levels.push(level.clone());
+        }
+        Self { levels }
+    }
+
+    /// The 32-byte root committing to every shard in the set.
+    pub fn root(&self) -> Hash {
+        self.levels.last().expect("at least one level")[0]
+    }
+
+    /// The inclusion proof for the leaf at `index`.
+    pub fn proof(&self, index: usize) -> MerkleProof {
+        let mut siblings = Vec::new();
+        let mut idx = index;
+        for level in &self.levels[..self.levels.len() - 1] {
+            let sibling_idx = if idx % 2 == 0 {
+                (idx + 1).min(level.len() - 1)
+            } else {
+                idx - 1
+            };
+            siblings.push(level[sibling_idx]);
+            idx /= 2;
+        }
+        MerkleProof { index, siblings }
+    }
+}
+
+/// Recompute the root from a shard and its inclusion proof. A
+/// downloading peer calls this as each shard arrives over GraphSync and
+/// only feeds shards that pass into `reconstruct`.
+pub fn verify_shard(root: &Hash, bytes: &[u8], proof: &MerkleProof) -> bool {
+    let mut hash = hash_leaf(bytes);
+    let mut idx = proof.index;
+    for sibling in &proof.siblings {
+        hash = if idx % 2 == 0 {
+            hash_node(&hash, sibling)
+        } else {
+            hash_node(sibling, &hash)
+        };
+        idx /= 2;
+    }
+    &hash == root
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn shards(n: usize) -> Vec<Vec<u8>> {
+        (0..n as u8).map(|i| vec![i; 8]).collect()
+    }
+
+    #[test]
+    fn every_leaf_verifies_against_the_root_with_an_odd_level() {
+        // 5 shards gives odd-length levels of 5 and then 3 nodes, exercising
+        // the last-node duplication in `from_shards`.
+        let data = shards(5);
+        let tree = MerkleTree::from_shards(&data);
+        let root = tree.root();
+
+        for (i, shard) in data.iter().enumerate() {
+            let proof = tree.proof(i);
+            assert!(verify_shard(&root, shard, &proof));
+        }
+    }
+
+    #[test]
+    fn a_flipped_byte_fails_verification() {
+        let data = shards(5);
+        let tree = MerkleTree::from_shards(&data);
+        let root = tree.root();
+
+        let proof = tree.proof(2);
+        let mut corrupted = data[2].clone();
+        corrupted[0] ^= 0xff;
+        assert!(!verify_shard(&root, &corrupted, &proof));
+    }
+
+    #[test]
+    fn a_proof_for_the_wrong_index_fails_verification() {
+        let data = shards(5);
+        let tree = MerkleTree::from_shards(&data);
+        let root = tree.root();
+
+        let mut proof = tree.proof(1);
+        proof.index = 3;
+        assert!(!verify_shard(&root, &data[1], &proof));
+    }
+}
@@ -0,0 +1,221 @@
+//! Striped multi-peer retrieval: spread shards across peers and
+//! re-request from another peer on stall or failed verification.
+
+use crate::ec::ErasureCoder;
+use crate::merkle::{verify_shard, Hash};
+use crate::retrieval::ShardLink;
+use futures::prelude::*;
+use graphsync::{GraphSync, GraphSyncEvent, Request};
+use ipld_traversal::blockstore::Blockstore;
+use libipld::Cid;
+use libp2p::swarm::{Swarm, SwarmEvent};
+use libp2p::PeerId;
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+#[derive(Debug)]
+pub enum MultiPeerError {
+    /// Every peer was tried for some shard and we still never reached
+    /// `num_data` verified shards.
+    InsufficientShards { have: usize, need: usize },
+    Reconstruct(reed_solomon_erasure::Error),
+}
+
+/// Assigns each shard to one of `peers` round-robin, requests it, and
+/// whenever a peer goes quiet for longer than `stall_timeout` or returns
+/// a shard that fails `verify_shard`, re-requests that shard's index
+/// from the next untried peer. Stops and reconstructs the moment
+/// `num_data` shards have verified.
+pub async fn retrieve_striped<B>(
+    swarm: &mut Swarm<GraphSync<B>>,
+    peers: &[PeerId],
+    shards: &[ShardLink],
+    merkle_root: Hash,
+    num_data: usize,
+    coder: &ErasureCoder,
+    stall_timeout: Duration,
+) -> Result<Vec<Vec<u8>>, MultiPeerError>
+where
+    B: Blockstore + Clone + Send + 'static,
+{
+    assert!(!peers.is_empty());
+
+    let by_cid: HashMap<Cid, &ShardLink> = shards.iter().map(|s| (s.cid, s)).collect();
+    let mut untried: HashMap<Cid, Vec<PeerId>> = HashMap::new();
+    // Per-shard: which peer currently holds the request and when it was sent,
+    // so a slow peer on one shard doesn't get masked by a responsive peer on
+    // another.
+    let mut outstanding: HashMap<Cid, (PeerId, Instant)> = HashMap::new();
+
+    for (i, shard) in shards.iter().enumerate() {
+        let mut candidates: Vec<PeerId> = peers.to_vec();
+        candidates.rotate_left(i % peers.len());
+        let first = candidates.remove(0);
+        untried.insert(shard.cid, candidates);
+
+        let req = Request::builder().root(shard.cid).build().unwrap();
+        swarm.behaviour_mut().request(first, req);
+        outstanding.insert(shard.cid, (first, Instant::now()));
+    }
+
+    let mut recovered: Vec<Option<Vec<u8>>> = vec![None; shards.len()];
+    let mut verified_count = 0;
+
+    while verified_count < num_data {
+        if outstanding.is_empty() {
+            return Err(MultiPeerError::InsufficientShards {
+                have: verified_count,
+                need: num_data,
+            });
+        }
+
+        // Wake up when the next shard's individual deadline is due, not on
+        // a fixed cadence, so a stalled shard gets reassigned promptly even
+        // while others are still within their timeout.
+        let next_deadline = outstanding
+            .values()
+            .map(|(_, sent_at)| *sent_at + stall_timeout)
+            .min()
+            .expect("outstanding is non-empty");
+        let wait = next_deadline.saturating_duration_since(Instant::now());
+
+        let sleep = async_std::task::sleep(wait);
+        pin_mut!(sleep);
+        let next_event = swarm.select_next_some();
+        pin_mut!(next_event);
+
+        match future::select(next_event, sleep).await {
+            future::Either::Right(_) => {
+                let now = Instant::now();
+                for cid in stalled_shards(&outstanding, stall_timeout, now) {
+                    reassign(swarm, cid, &mut outstanding, &mut untried);
+                }
+            }
+            future::Either::Left((SwarmEvent::Behaviour(GraphSyncEvent::Progress {
+                link,
+                data,
+                ..
+            }), _)) => {
+                let Some(shard) = by_cid.get(&link) else {
+                    continue;
+                };
+                if verify_shard(&merkle_root, &data, &shard.proof) {
+                    if recovered[shard.index].is_none() {
+                        recovered[shard.index] = Some(data);
+                        verified_count += 1;
+                    }
+                    outstanding.remove(&link);
+                } else {
+                    reassign(swarm, link, &mut outstanding, &mut untried);
+                }
+            }
+            future::Either::Left(_) => continue,
+        }
+    }
+
+    for cid in outstanding.into_keys() {
+        swarm.behaviour_mut().cancel(cid);
+    }
+
+    coder
+        .reconstruct(&mut recovered)
+        .map_err(MultiPeerError::Reconstruct)?;
+
+    Ok(recovered
+        .into_iter()
+        .take(num_data)
+        .map(|s| s.expect("reconstruct fills every shard"))
+        .collect())
+}
+
+/// Which outstanding shards have gone past `stall_timeout` as of `now`.
+/// Pulled out of the retry loop so the deadline math is testable without
+/// a real `Swarm`.
+fn stalled_shards(
+    outstanding: &HashMap<Cid, (PeerId, Instant)>,
+    stall_timeout: Duration,
+    now: Instant,
+) -> Vec<Cid> {
+    outstanding
+        .iter()
+        .filter(|(_, (_, sent_at))| now.duration_since(*sent_at) >= stall_timeout)
+        .map(|(cid, _)| *cid)
+        .collect()
+}
+
+/// Pop the next untried peer for `cid`, if any remain.
+fn next_untried_peer(cid: Cid, untried: &mut HashMap<Cid, Vec<PeerId>>) -> Option<PeerId> {
+    untried.get_mut(&cid).and_then(Vec::pop)
+}
+
+/// Move `cid` from its current (failed) peer to the next untried one,
+/// cancelling the abandoned peer's request so it doesn't keep sending a
+/// shard we've already given up on. If no untried peers remain, the
+/// shard is dropped from `outstanding` and `retrieve_striped` will count
+/// it missing.
+fn reassign<B>(
+    swarm: &mut Swarm<GraphSync<B>>,
+    cid: Cid,
+    outstanding: &mut HashMap<Cid, (PeerId, Instant)>,
+    untried: &mut HashMap<Cid, Vec<PeerId>>,
+) where
+    B: Blockstore + Clone + Send + 'static,
+{
+    if outstanding.remove(&cid).is_some() {
+        swarm.behaviour_mut().cancel(cid);
+    }
+    if let Some(next) = next_untried_peer(cid, untried) {
+        let req = Request::builder().root(cid).build().unwrap();
+        swarm.behaviour_mut().request(next, req);
+        outstanding.insert(cid, (next, Instant::now()));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stalled_shards_only_reports_shards_past_the_timeout() {
+        let cid: Cid = "bafyreigaknpexyvxt76zgkitavbwx6ejgfheup5oybpm77f5wkdz5c2r4m"
+            .parse()
+            .unwrap();
+        let other: Cid = "bafyreibm6jg3ux5qumhcn2b3flc3tyu6dmlgsdf4hmp2b4g25p6e6b7uly"
+            .parse()
+            .unwrap();
+        let stall_timeout = Duration::from_secs(1);
+        let sent_at = Instant::now();
+        let mut outstanding = HashMap::new();
+        outstanding.insert(cid, (PeerId::random(), sent_at));
+        outstanding.insert(other, (PeerId::random(), sent_at));
+
+        // Before the timeout has elapsed, nothing is stalled.
+        assert!(stalled_shards(&outstanding, stall_timeout, sent_at).is_empty());
+
+        // Once past it, every outstanding shard sent at `sent_at` is stalled.
+        let past_deadline = sent_at + Duration::from_secs(2);
+        let mut stalled = stalled_shards(&outstanding, stall_timeout, past_deadline);
+        stalled.sort();
+        let mut expected = vec![cid, other];
+        expected.sort();
+        assert_eq!(stalled, expected);
+    }
+
+    #[test]
+    fn next_untried_peer_rotates_through_candidates_then_gives_up() {
+        let cid: Cid = "bafyreigaknpexyvxt76zgkitavbwx6ejgfheup5oybpm77f5wkdz5c2r4m"
+            .parse()
+            .unwrap();
+        let first = PeerId::random();
+        let second = PeerId::random();
+        let mut untried = HashMap::new();
+        untried.insert(cid, vec![first, second]);
+
+        // This is what both the stall path and the failed-verification
+        // path drive `reassign` through: pop candidates one at a time...
+        assert_eq!(next_untried_peer(cid, &mut untried), Some(second));
+        assert_eq!(next_untried_peer(cid, &mut untried), Some(first));
+        // ...until none remain, at which point the shard can't be reassigned.
+        assert_eq!(next_untried_peer(cid, &mut untried), None);
+    }
+}
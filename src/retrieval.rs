@@ -0,0 +1,208 @@
+//! Minimum-`k` partial retrieval over GraphSync: request shards
+//! individually and stop once `num_data` have verified.
+
+use crate::ec::ErasureCoder;
+use crate::merkle::{verify_shard, Hash, MerkleProof};
+use futures::prelude::*;
+use graphsync::{GraphSync, GraphSyncEvent, Request};
+use ipld_traversal::blockstore::Blockstore;
+use libipld::Cid;
+use libp2p::swarm::{Swarm, SwarmEvent};
+use libp2p::PeerId;
+use std::collections::HashMap;
+
+/// One shard of an erasure set: its link CID and its inclusion proof
+/// against the set's Merkle root.
+pub struct ShardLink {
+    pub index: usize,
+    pub cid: Cid,
+    pub proof: MerkleProof,
+}
+
+#[derive(Debug)]
+pub enum RetrievalError {
+    /// Every requested shard has answered (verified or not) and a
+    /// `GraphSyncEvent::Completed` arrived before `num_data` shards
+    /// verified. A peer that disconnects is not detected; the call keeps
+    /// waiting for events.
+    InsufficientShards { have: usize, need: usize },
+    Reconstruct(reed_solomon_erasure::Error),
+}
+
+/// Request `shards` from `peer`, stopping as soon as `num_data` of them
+/// have arrived and verified against `merkle_root`, then reconstruct the
+/// original data shards. Any still-outstanding requests are cancelled
+/// once enough shards are in hand, so the caller pays for ~`num_data`
+/// shards of bandwidth instead of the full `num_data + num_parity`.
+pub async fn retrieve_min_k<B>(
+    swarm: &mut Swarm<GraphSync<B>>,
+    peer: PeerId,
+    shards: &[ShardLink],
+    merkle_root: Hash,
+    num_data: usize,
+    coder: &ErasureCoder,
+) -> Result<Vec<Vec<u8>>, RetrievalError>
+where
+    B: Blockstore + Clone + Send + 'static,
+{
+    let mut pending: HashMap<Cid, &ShardLink> = shards.iter().map(|s| (s.cid, s)).collect();
+    let mut recovered: Vec<Option<Vec<u8>>> = vec![None; shards.len()];
+    let mut verified_count = 0;
+
+    for shard in shards {
+        let req = Request::builder().root(shard.cid).build().unwrap();
+        swarm.behaviour_mut().request(peer, req);
+    }
+
+    while verified_count < num_data {
+        match swarm.select_next_some().await {
+            SwarmEvent::Behaviour(GraphSyncEvent::Progress { link, data, .. }) => {
+                if accept_shard(&mut pending, &mut recovered, &merkle_root, link, data) {
+                    verified_count += 1;
+                }
+            }
+            SwarmEvent::Behaviour(GraphSyncEvent::Completed { .. }) if pending.is_empty() => break,
+            _ => continue,
+        }
+    }
+
+    if verified_count < num_data {
+        return Err(RetrievalError::InsufficientShards {
+            have: verified_count,
+            need: num_data,
+        });
+    }
+
+    // We have enough; stop waiting on the shards we never needed.
+    for cid in pending.into_keys() {
+        swarm.behaviour_mut().cancel(cid);
+    }
+
+    coder
+        .reconstruct(&mut recovered)
+        .map_err(RetrievalError::Reconstruct)?;
+
+    Ok(recovered
+        .into_iter()
+        .take(num_data)
+        .map(|s| s.expect("reconstruct fills every shard"))
+        .collect())
+}
+
+/// Check an arrived shard against `merkle_root` and, if it verifies and
+/// was still pending, record it into `recovered`. Returns whether it
+/// counted toward `verified_count`. Pulled out of the event loop so the
+/// accounting is testable without a real `Swarm`.
+fn accept_shard(
+    pending: &mut HashMap<Cid, &ShardLink>,
+    recovered: &mut [Option<Vec<u8>>],
+    merkle_root: &Hash,
+    link: Cid,
+    data: Vec<u8>,
+) -> bool {
+    let Some(shard) = pending.remove(&link) else {
+        return false;
+    };
+    if !verify_shard(merkle_root, &data, &shard.proof) {
+        return false;
+    }
+    recovered[shard.index] = Some(data);
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::merkle::MerkleTree;
+
+    fn shard_links(data: &[Vec<u8>], cids: &[Cid]) -> (MerkleTree, Vec<ShardLink>) {
+        let tree = MerkleTree::from_shards(data);
+        let links = cids
+            .iter()
+            .enumerate()
+            .map(|(i, &cid)| ShardLink {
+                index: i,
+                cid,
+                proof: tree.proof(i),
+            })
+            .collect();
+        (tree, links)
+    }
+
+    fn test_cids(n: usize) -> Vec<Cid> {
+        [
+            "bafyreigaknpexyvxt76zgkitavbwx6ejgfheup5oybpm77f5wkdz5c2r4m",
+            "bafyreibm6jg3ux5qumhcn2b3flc3tyu6dmlgsdf4hmp2b4g25p6e6b7uly",
+            "bafyreih6aphn7uyhojn6wgnyoqgbh4gn6uwpnepn2xyhrzk6mpvqgfhq2u",
+        ]
+        .iter()
+        .take(n)
+        .map(|s| s.parse().unwrap())
+        .collect()
+    }
+
+    #[test]
+    fn a_verified_shard_is_recorded_and_counts() {
+        let data = vec![vec![1u8; 4], vec![2u8; 4], vec![3u8; 4]];
+        let cids = test_cids(3);
+        let (tree, links) = shard_links(&data, &cids);
+        let root = tree.root();
+
+        let mut pending: HashMap<Cid, &ShardLink> = links.iter().map(|s| (s.cid, s)).collect();
+        let mut recovered: Vec<Option<Vec<u8>>> = vec![None; links.len()];
+
+        assert!(accept_shard(
+            &mut pending,
+            &mut recovered,
+            &root,
+            cids[1],
+            data[1].clone(),
+        ));
+        assert_eq!(recovered[1], Some(data[1].clone()));
+        assert!(!pending.contains_key(&cids[1]));
+    }
+
+    #[test]
+    fn a_shard_that_fails_verification_is_dropped_and_not_recorded() {
+        let data = vec![vec![1u8; 4], vec![2u8; 4], vec![3u8; 4]];
+        let cids = test_cids(3);
+        let (tree, links) = shard_links(&data, &cids);
+        let root = tree.root();
+
+        let mut pending: HashMap<Cid, &ShardLink> = links.iter().map(|s| (s.cid, s)).collect();
+        let mut recovered: Vec<Option<Vec<u8>>> = vec![None; links.len()];
+
+        let mut corrupted = data[1].clone();
+        corrupted[0] ^= 0xff;
+        assert!(!accept_shard(
+            &mut pending,
+            &mut recovered,
+            &root,
+            cids[1],
+            corrupted,
+        ));
+        assert_eq!(recovered[1], None);
+    }
+
+    #[test]
+    fn a_shard_for_an_unknown_cid_is_ignored() {
+        let data = vec![vec![1u8; 4], vec![2u8; 4]];
+        let cids = test_cids(2);
+        let (tree, links) = shard_links(&data, &cids[..2]);
+        let root = tree.root();
+
+        let mut pending: HashMap<Cid, &ShardLink> = links.iter().map(|s| (s.cid, s)).collect();
+        let mut recovered: Vec<Option<Vec<u8>>> = vec![None; links.len()];
+
+        let unknown: Cid = "bafyreih6aphn7uyhojn6wgnyoqgbh4gn6uwpnepn2xyhrzk6mpvqgfhq2u"
+            .parse()
+            .unwrap();
+        assert!(!accept_shard(
+            &mut pending,
+            &mut recovered,
+            &root,
+            unknown,
+            vec![9u8; 4],
+        ));
+    }
+}
@@ -0,0 +1,249 @@
+//! Incremental append-and-encode with a persistent Merkle Mountain Range:
+//! each append folds in new shards without recomputing prior subtrees.
+
+use crate::ec::ErasureCoder;
+use crate::merkle::{hash_leaf, hash_node, Hash};
+
+/// A complete subtree of `2^height` leaves within the append-only
+/// history, covering the contiguous global leaf range starting at `base`.
+struct Peak {
+    height: usize,
+    base: usize,
+    /// `nodes[level]` holds this subtree's hashes at that level; `nodes[0]`
+    /// is the leaves, `nodes[height]` is a single element: the peak root.
+    nodes: Vec<Vec<Hash>>,
+}
+
+impl Peak {
+    fn leaf(base: usize, hash: Hash) -> Self {
+        Self {
+            height: 0,
+            base,
+            nodes: vec![vec![hash]],
+        }
+    }
+
+    fn root(&self) -> Hash {
+        self.nodes[self.height][0]
+    }
+
+    fn leaf_count(&self) -> usize {
+        1 << self.height
+    }
+
+    /// Merge two equal-height, adjacent peaks (`left` immediately
+    /// followed by `right`) into the next taller peak, reusing both
+    /// subtrees' node rows instead of rehashing their leaves.
+    fn merge(left: Peak, right: Peak) -> Peak {
+        debug_assert_eq!(left.height, right.height);
+        debug_assert_eq!(left.base + left.leaf_count(), right.base);
+
+        let mut nodes = Vec::with_capacity(left.height + 2);
+        for level in 0..=left.height {
+            let mut row = left.nodes[level].clone();
+            row.extend(right.nodes[level].iter().copied());
+            nodes.push(row);
+        }
+        nodes.push(vec![hash_node(&left.root(), &right.root())]);
+
+        Peak {
+            height: left.height + 1,
+            base: left.base,
+            nodes,
+        }
+    }
+
+    /// Sibling hashes from `local_index` up to this peak's root.
+    fn proof_within(&self, local_index: usize) -> Vec<Hash> {
+        let mut siblings = Vec::with_capacity(self.height);
+        let mut idx = local_index;
+        for level in 0..self.height {
+            siblings.push(self.nodes[level][idx ^ 1]);
+            idx /= 2;
+        }
+        siblings
+    }
+}
+
+/// Fold a new leaf hash into `peaks`, cascading merges while the two
+/// most-recent peaks share a height — the same carry as incrementing a
+/// binary counter. `peaks` stays ordered from tallest to shortest.
+fn add_leaf(peaks: &mut Vec<Peak>, base: usize, hash: Hash) {
+    peaks.push(Peak::leaf(base, hash));
+    while peaks.len() >= 2 {
+        let n = peaks.len();
+        if peaks[n - 1].height != peaks[n - 2].height {
+            break;
+        }
+        let right = peaks.pop().unwrap();
+        let left = peaks.pop().unwrap();
+        peaks.push(Peak::merge(left, right));
+    }
+}
+
+/// Bag a set of peak roots (tallest first) into a single root.
+fn bag(peak_roots: &[Hash]) -> Hash {
+    let mut roots = peak_roots.iter();
+    let mut root = *roots.next().expect("at least one peak");
+    for r in roots {
+        root = hash_node(&root, r);
+    }
+    root
+}
+
+/// Inclusion proof for a historical shard against the encoder's root at
+/// the time the proof is requested (later appends change `peak_roots`
+/// and invalidate a proof generated earlier).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HistoryProof {
+    local_index: usize,
+    peak_pos: usize,
+    in_peak_siblings: Vec<Hash>,
+    peak_roots: Vec<Hash>,
+}
+
+/// Verify a historical shard against a root returned by `append`.
+pub fn verify_in_history(root: &Hash, bytes: &[u8], proof: &HistoryProof) -> bool {
+    let mut hash = hash_leaf(bytes);
+    let mut idx = proof.local_index;
+    for sibling in &proof.in_peak_siblings {
+        hash = if idx % 2 == 0 {
+            hash_node(&hash, sibling)
+        } else {
+            hash_node(sibling, &hash)
+        };
+        idx /= 2;
+    }
+
+    if proof.peak_pos >= proof.peak_roots.len() {
+        return false;
+    }
+    let mut roots = proof.peak_roots.clone();
+    roots[proof.peak_pos] = hash;
+    &bag(&roots) == root
+}
+
+/// Encodes a growing dataset as a sequence of erasure-coded stripes,
+/// committing every shard it has ever produced to an append-only Merkle
+/// history.
+pub struct StreamingEncoder {
+    coder: ErasureCoder,
+    num_data: usize,
+    num_parity: usize,
+    shard_size: usize,
+    buffer: Vec<u8>,
+    peaks: Vec<Peak>,
+    shards: Vec<Vec<u8>>,
+}
+
+impl StreamingEncoder {
+    pub fn new(
+        num_data: usize,
+        num_parity: usize,
+        shard_size: usize,
+    ) -> Result<Self, reed_solomon_erasure::Error> {
+        Ok(Self {
+            coder: ErasureCoder::new(num_data, num_parity)?,
+            num_data,
+            num_parity,
+            shard_size,
+            buffer: Vec::new(),
+            peaks: Vec::new(),
+            shards: Vec::new(),
+        })
+    }
+
+    /// Append bytes to the stream. Each time a full `num_data`-shard
+    /// stripe has accumulated, its parity shards are computed, all `n`
+    /// shards in the stripe are folded into the history, and the
+    /// resulting root is returned. Returns `None` while `bytes` is still
+    /// short of completing the next stripe.
+    pub fn append(&mut self, bytes: &[u8]) -> Option<Hash> {
+        self.buffer.extend_from_slice(bytes);
+
+        let stripe_len = self.shard_size * self.num_data;
+        let mut root = None;
+        while self.buffer.len() >= stripe_len {
+            let stripe: Vec<u8> = self.buffer.drain(..stripe_len).collect();
+            let mut shards: Vec<Vec<u8>> =
+                stripe.chunks(self.shard_size).map(<[u8]>::to_vec).collect();
+            shards.resize(self.num_data + self.num_parity, vec![0u8; self.shard_size]);
+            self.coder.encode(&mut shards).unwrap();
+
+            for shard in shards {
+                let base = self.shards.len();
+                add_leaf(&mut self.peaks, base, hash_leaf(&shard));
+                self.shards.push(shard);
+            }
+            root = Some(self.root());
+        }
+        root
+    }
+
+    /// The root committing to every shard appended so far.
+    pub fn root(&self) -> Hash {
+        let peak_roots: Vec<Hash> = self.peaks.iter().map(Peak::root).collect();
+        bag(&peak_roots)
+    }
+
+    /// An inclusion proof for the shard at global index `index` against
+    /// the encoder's current root.
+    pub fn proof(&self, index: usize) -> HistoryProof {
+        let (peak_pos, peak) = self
+            .peaks
+            .iter()
+            .enumerate()
+            .find(|(_, p)| index >= p.base && index < p.base + p.leaf_count())
+            .expect("index out of range");
+        let local_index = index - peak.base;
+
+        HistoryProof {
+            local_index,
+            peak_pos,
+            in_peak_siblings: peak.proof_within(local_index),
+            peak_roots: self.peaks.iter().map(Peak::root).collect(),
+        }
+    }
+
+    /// The bytes of the shard at global index `index`.
+    pub fn shard(&self, index: usize) -> &[u8] {
+        &self.shards[index]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn proof_for_an_older_merged_peak_round_trips() {
+        let mut encoder = StreamingEncoder::new(2, 1, 4).unwrap();
+
+        // Five stripes of 3 shards each cascades through several peak
+        // merges, so shard 0 ends up buried inside a taller peak well
+        // before the latest root is taken.
+        let mut root = None;
+        for i in 0..5u8 {
+            root = encoder.append(&[i; 8]);
+        }
+        let root = root.unwrap();
+
+        let proof = encoder.proof(0);
+        assert!(verify_in_history(&root, encoder.shard(0), &proof));
+    }
+
+    #[test]
+    fn a_flipped_byte_fails_verification() {
+        let mut encoder = StreamingEncoder::new(2, 1, 4).unwrap();
+        let mut root = None;
+        for i in 0..5u8 {
+            root = encoder.append(&[i; 8]);
+        }
+        let root = root.unwrap();
+
+        let proof = encoder.proof(0);
+        let mut corrupted = encoder.shard(0).to_vec();
+        corrupted[0] ^= 0xff;
+        assert!(!verify_in_history(&root, &corrupted, &proof));
+    }
+}